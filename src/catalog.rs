@@ -0,0 +1,230 @@
+//! Parsing of real star catalogs into `Star`s.
+//!
+//! Catalogs are plain CSVs with a header row; columns of interest are looked
+//! up by name (`ra`, `dec`, `dist`, `mag`/`absmag`, `ci`/`spect`) so minor
+//! layout differences don't matter. Right ascension is expected in decimal
+//! hours, declination in decimal degrees, and distance in parsecs — the HYG
+//! database's layout. The Gliese Catalogue of Nearby Stars uses different
+//! column names and sexagesimal coordinates, so loading a native Gliese CSV
+//! requires converting it to this layout first.
+
+use std::f64;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use na;
+
+use super::{Star, Result, ResultExt};
+use super::{SOLAR_LUMINOSITY, GALAXY_RADIUS};
+
+const PARSEC_METERS: f64 = 3.0856775814913673e16;
+/// Conversion from parsecs to the galaxy's internal distance unit.
+const PARSEC_SCALE: f32 = (PARSEC_METERS / GALAXY_RADIUS) as f32;
+
+/// Clamp range for derived temperatures, matching `color::BlackbodyTable`'s
+/// supported range so a malformed `ci` can't produce a huge, negative, or
+/// infinite temperature (its denominator can cross zero for outlier inputs).
+const TEMPERATURE_MIN_K: f32 = 1000.0;
+const TEMPERATURE_MAX_K: f32 = 40000.0;
+
+struct Columns {
+    ra: usize,
+    dec: usize,
+    dist: usize,
+    mag: Option<usize>,
+    absmag: Option<usize>,
+    ci: Option<usize>,
+    spect: Option<usize>,
+}
+
+fn find(header: &[String], name: &str) -> Option<usize> {
+    header.iter().position(|h| h.eq_ignore_ascii_case(name))
+}
+
+fn columns(header: &[String]) -> Result<Columns> {
+    Ok(Columns {
+        ra: find(header, "ra").chain_err(|| "catalog is missing a 'ra' column")?,
+        dec: find(header, "dec").chain_err(|| "catalog is missing a 'dec' column")?,
+        dist: find(header, "dist").chain_err(|| "catalog is missing a 'dist' column")?,
+        mag: find(header, "mag"),
+        absmag: find(header, "absmag"),
+        ci: find(header, "ci"),
+        spect: find(header, "spect"),
+    })
+}
+
+/// Effective temperature from a B-V color index, via Ballesteros' formula.
+/// Clamped since the formula's denominators can cross zero for outlier `ci`.
+fn temperature_from_ci(ci: f32) -> f32 {
+    let t = 4600.0 * (1.0 / (0.92 * ci + 1.7) + 1.0 / (0.92 * ci + 0.62));
+    t.max(TEMPERATURE_MIN_K).min(TEMPERATURE_MAX_K)
+}
+
+/// Effective temperature from a spectral type string (e.g. "G2V", "M4"),
+/// using the first letter as a coarse main-sequence lookup.
+fn temperature_from_spect(spect: &str) -> f32 {
+    match spect.trim().chars().next() {
+        Some('O') => 30000.0,
+        Some('B') => 15000.0,
+        Some('A') => 9000.0,
+        Some('F') => 7000.0,
+        Some('G') => 5700.0,
+        Some('K') => 4500.0,
+        Some('M') => 3200.0,
+        _ => 5700.0,
+    }
+}
+
+/// Loads a HYG-layout CSV catalog, converting each entry into a
+/// `Star` positioned relative to Sol at the origin.
+pub fn load(path: &Path) -> Result<Vec<Star>> {
+    let file = File::open(path).chain_err(|| "failed to open catalog")?;
+    let mut lines = BufReader::new(file).lines();
+
+    let header: Vec<String> = lines.next()
+        .chain_err(|| "catalog is empty")?
+        .chain_err(|| "failed to read catalog header")?
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .collect();
+    let columns = columns(&header)?;
+
+    let mut stars = Vec::new();
+    for line in lines {
+        let line = line.chain_err(|| "failed to read catalog row")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').collect();
+        let ra_hours: f64 = match fields.get(columns.ra).and_then(|s| s.trim().parse().ok()) {
+            Some(v) => v,
+            None => continue,
+        };
+        let dec_deg: f64 = match fields.get(columns.dec).and_then(|s| s.trim().parse().ok()) {
+            Some(v) => v,
+            None => continue,
+        };
+        let dist_pc: f64 = match fields.get(columns.dist).and_then(|s| s.trim().parse().ok()) {
+            Some(v) => v,
+            None => continue,
+        };
+        if !dist_pc.is_finite() || dist_pc <= 0.0 {
+            continue;
+        }
+
+        let ra_rad = ra_hours * 15.0 * f64::consts::PI / 180.0;
+        let dec_rad = dec_deg * f64::consts::PI / 180.0;
+        let x = dist_pc * dec_rad.cos() * ra_rad.cos();
+        let y = dist_pc * dec_rad.cos() * ra_rad.sin();
+        let z = dist_pc * dec_rad.sin();
+        let position = na::Point3::new((x as f32) * PARSEC_SCALE,
+                                        (z as f32) * PARSEC_SCALE,
+                                        (y as f32) * PARSEC_SCALE);
+
+        let absmag = columns.absmag
+            .and_then(|i| fields.get(i))
+            .and_then(|s| s.trim().parse::<f64>().ok())
+            .or_else(|| {
+                columns.mag
+                    .and_then(|i| fields.get(i))
+                    .and_then(|s| s.trim().parse::<f64>().ok())
+                    .map(|apparent| apparent - 5.0 * (dist_pc / 10.0).log10())
+            });
+        let absmag = match absmag {
+            Some(m) => m,
+            None => continue,
+        };
+        let luminosity = 10f64.powf(0.4 * (4.83 - absmag));
+
+        let temperature = columns.ci
+            .and_then(|i| fields.get(i))
+            .and_then(|s| s.trim().parse::<f32>().ok())
+            .map(temperature_from_ci)
+            .or_else(|| {
+                columns.spect
+                    .and_then(|i| fields.get(i))
+                    .filter(|s| !s.trim().is_empty())
+                    .map(|s| temperature_from_spect(s))
+            })
+            .unwrap_or(5777.0);
+
+        stars.push(Star {
+            position,
+            intensity: (luminosity / (4.0 * f64::consts::PI)) as f32,
+            temperature,
+        });
+    }
+
+    Ok(stars)
+}
+
+#[cfg(test)]
+fn load_str(contents: &str, test_name: &str) -> Result<Vec<Star>> {
+    use std::io::Write;
+    let path = std::env::temp_dir().join(format!("starbox_catalog_test_{}.csv", test_name));
+    {
+        let mut file = File::create(&path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+    }
+    let result = load(&path);
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+#[test]
+fn columns_are_found_by_case_insensitive_name() {
+    let header = vec!["RA".to_string(), "Dec".to_string(), "Dist".to_string()];
+    let columns = columns(&header).unwrap();
+    assert_eq!(columns.ra, 0);
+    assert_eq!(columns.dec, 1);
+    assert_eq!(columns.dist, 2);
+    assert!(columns.mag.is_none());
+}
+
+#[test]
+fn ra_dec_dist_convert_to_cartesian() {
+    // RA 0h, Dec 0deg points along +x; distance in parsecs scales by PARSEC_SCALE.
+    let stars = load_str("ra,dec,dist,absmag\n0,0,10,4.83\n", "cartesian").unwrap();
+    assert_eq!(stars.len(), 1);
+    let expected_x = 10.0 * PARSEC_SCALE;
+    assert!((stars[0].position.x - expected_x).abs() < expected_x * 1e-4);
+    assert!(stars[0].position.y.abs() < 1e-3);
+    assert!(stars[0].position.z.abs() < 1e-3);
+}
+
+#[test]
+fn absmag_falls_back_to_apparent_mag_and_distance() {
+    // A mag-6 star at 10pc has absmag 6 by definition (distance modulus 0).
+    let stars = load_str("ra,dec,dist,mag\n0,0,10,6.0\n", "absmag_fallback").unwrap();
+    assert_eq!(stars.len(), 1);
+    let expected_luminosity = 10f64.powf(0.4 * (4.83 - 6.0));
+    let expected_intensity = (expected_luminosity / (4.0 * f64::consts::PI)) as f32;
+    assert!((stars[0].intensity - expected_intensity).abs() < expected_intensity * 1e-4);
+}
+
+#[test]
+fn temperature_from_ci_clamps_outlier_values() {
+    // Near -0.674 and -1.848 the formula's denominators cross zero.
+    assert!(temperature_from_ci(-0.674).is_finite());
+    assert!(temperature_from_ci(-1.848).is_finite());
+    for &ci in &[-10.0, -1.848, -0.674, 0.65, 10.0] {
+        let t = temperature_from_ci(ci);
+        assert!(t >= TEMPERATURE_MIN_K && t <= TEMPERATURE_MAX_K, "{} out of range for ci={}", t, ci);
+    }
+}
+
+#[test]
+fn temperature_from_ci_and_spect_convert_known_values() {
+    assert!((temperature_from_ci(0.65) - 5772.0).abs() < 50.0);
+    assert_eq!(temperature_from_spect("G2V"), 5700.0);
+    assert_eq!(temperature_from_spect(""), 5700.0);
+}
+
+#[test]
+fn short_rows_are_skipped_instead_of_panicking() {
+    // The second row is missing the `absmag` field entirely; the third is
+    // missing `dist` too. Neither should panic, just be skipped.
+    let stars = load_str("ra,dec,dist,absmag\n0,0,10,4.83\n1,2,3\n1,2\n", "short_rows").unwrap();
+    assert_eq!(stars.len(), 1);
+}
@@ -0,0 +1,122 @@
+//! Conversion from blackbody temperature to linear sRGB chromaticity.
+
+const PLANCK_H: f64 = 6.62607015e-34;
+const SPEED_OF_LIGHT: f64 = 2.99792458e8;
+const BOLTZMANN_K: f64 = 1.380649e-23;
+
+const WAVELENGTH_MIN_NM: f64 = 380.0;
+const WAVELENGTH_MAX_NM: f64 = 780.0;
+const WAVELENGTH_STEP_NM: f64 = 5.0;
+
+const TABLE_MIN_K: f32 = 1000.0;
+const TABLE_MAX_K: f32 = 40000.0;
+const TABLE_STEP_K: f32 = 100.0;
+
+/// Spectral radiance per Planck's law. Units are arbitrary; only the relative
+/// shape across wavelengths matters, since the result is renormalized to unit
+/// luminance.
+fn planck(wavelength_m: f64, temperature_k: f64) -> f64 {
+    let l5 = wavelength_m.powi(5);
+    let exponent = PLANCK_H * SPEED_OF_LIGHT / (wavelength_m * BOLTZMANN_K * temperature_k);
+    (2.0 * PLANCK_H * SPEED_OF_LIGHT * SPEED_OF_LIGHT) / (l5 * (exponent.exp() - 1.0))
+}
+
+/// CIE 1931 standard observer color-matching functions, via the multi-lobe
+/// Gaussian fit of Wyman, Sloan & Shirley (2013), avoiding the need to embed
+/// the full tabulated data.
+fn cie_xyz(wavelength_nm: f64) -> (f64, f64, f64) {
+    fn gauss(x: f64, mean: f64, sigma_lo: f64, sigma_hi: f64) -> f64 {
+        let sigma = if x < mean { sigma_lo } else { sigma_hi };
+        (-0.5 * ((x - mean) / sigma).powi(2)).exp()
+    }
+    let x = 1.056 * gauss(wavelength_nm, 599.8, 37.9, 31.0)
+        + 0.362 * gauss(wavelength_nm, 442.0, 16.0, 26.7)
+        - 0.065 * gauss(wavelength_nm, 501.1, 20.4, 26.2);
+    let y = 0.821 * gauss(wavelength_nm, 568.8, 46.9, 40.5)
+        + 0.286 * gauss(wavelength_nm, 530.9, 16.3, 31.1);
+    let z = 1.217 * gauss(wavelength_nm, 437.0, 11.8, 36.0)
+        + 0.681 * gauss(wavelength_nm, 459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+/// Integrates Planck's law against the CIE color-matching functions to get the
+/// CIE XYZ tristimulus values of a `temperature_k` blackbody, normalized to
+/// unit luminance (`Y == 1`). Callers scale the result by actual irradiance.
+fn blackbody_xyz(temperature_k: f64) -> (f64, f64, f64) {
+    let mut x = 0.0;
+    let mut y = 0.0;
+    let mut z = 0.0;
+    let mut wavelength_nm = WAVELENGTH_MIN_NM;
+    while wavelength_nm <= WAVELENGTH_MAX_NM {
+        let radiance = planck(wavelength_nm * 1e-9, temperature_k);
+        let (cx, cy, cz) = cie_xyz(wavelength_nm);
+        x += radiance * cx;
+        y += radiance * cy;
+        z += radiance * cz;
+        wavelength_nm += WAVELENGTH_STEP_NM;
+    }
+    if y > 0.0 {
+        (x / y, 1.0, z / y)
+    } else {
+        (0.0, 0.0, 0.0)
+    }
+}
+
+/// CIE XYZ to linear sRGB, clamping components that fall outside the gamut.
+fn xyz_to_linear_srgb(x: f64, y: f64, z: f64) -> (f32, f32, f32) {
+    let r = 3.2406 * x - 1.5372 * y - 0.4986 * z;
+    let g = -0.9689 * x + 1.8758 * y + 0.0415 * z;
+    let b = 0.0557 * x - 0.2040 * y + 1.0570 * z;
+    (r.max(0.0) as f32, g.max(0.0) as f32, b.max(0.0) as f32)
+}
+
+/// A precomputed lookup table of blackbody chromaticity, since stellar
+/// temperatures recur far more often than they're unique.
+pub struct BlackbodyTable {
+    entries: Vec<(f32, f32, f32)>,
+}
+
+impl BlackbodyTable {
+    pub fn new() -> Self {
+        let steps = ((TABLE_MAX_K - TABLE_MIN_K) / TABLE_STEP_K) as usize + 1;
+        let entries = (0..steps)
+            .map(|i| {
+                let t = TABLE_MIN_K as f64 + i as f64 * TABLE_STEP_K as f64;
+                let (x, y, z) = blackbody_xyz(t);
+                xyz_to_linear_srgb(x, y, z)
+            })
+            .collect();
+        BlackbodyTable { entries }
+    }
+
+    /// Unit-luminance linear sRGB chromaticity for `temperature_k`, linearly
+    /// interpolated between table entries.
+    pub fn rgb(&self, temperature_k: f32) -> (f32, f32, f32) {
+        let t = temperature_k.max(TABLE_MIN_K).min(TABLE_MAX_K);
+        let pos = (t - TABLE_MIN_K) / TABLE_STEP_K;
+        let i = pos.floor() as usize;
+        let frac = pos - i as f32;
+        let lo = self.entries[i];
+        let hi = self.entries[(i + 1).min(self.entries.len() - 1)];
+        (lo.0 + (hi.0 - lo.0) * frac,
+         lo.1 + (hi.1 - lo.1) * frac,
+         lo.2 + (hi.2 - lo.2) * frac)
+    }
+}
+
+#[test]
+fn sunlike_is_roughly_white() {
+    let table = BlackbodyTable::new();
+    let (r, g, b) = table.rgb(5777.0);
+    assert!(r > 0.0 && g > 0.0 && b > 0.0);
+    assert!((r - g).abs() < 0.5);
+    assert!((g - b).abs() < 0.5);
+}
+
+#[test]
+fn hot_stars_are_bluer_than_cool_stars() {
+    let table = BlackbodyTable::new();
+    let (hot_r, _, hot_b) = table.rgb(20000.0);
+    let (cool_r, _, cool_b) = table.rgb(3000.0);
+    assert!(hot_b / hot_r > cool_b / cool_r);
+}
@@ -5,21 +5,45 @@ extern crate error_chain;
 extern crate nalgebra as na;
 extern crate rand;
 extern crate half;
+extern crate rayon;
+
+mod color;
+mod catalog;
 
 error_chain! {}
 
+pub(crate) const SOLAR_LUMINOSITY: f64 = 3.828e26;
+pub(crate) const GALAXY_RADIUS: f64 = 1e21;
+
+/// Upper bound on `--psf-radius`, chosen so a single star's kernel never
+/// exceeds a few thousand samples even if a user fat-fingers the value.
+const MAX_PSF_RADIUS: f32 = 32.0;
+
 use std::fs::File;
 use std::path::Path;
 use std::f64;
 
 use clap::{Arg, App};
-use rand::{Rng, Rand};
-use rand::distributions::{IndependentSample, Normal, Exp};
+use rand::{Rng, Rand, SeedableRng, XorShiftRng};
+use rand::distributions::{IndependentSample, Normal};
 use rand::distributions::normal::StandardNormal;
 use half::f16;
+use rayon::prelude::*;
+
+use color::BlackbodyTable;
 
 quick_main!(run);
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ColorMode {
+    /// Luminance (`Y`) plus temperature (`T`), as consumed by a shader that
+    /// does its own blackbody lookup.
+    Luminance,
+    /// Real linear-sRGB chromaticity (`R`, `G`, `B`), derived from blackbody
+    /// temperature via `color::BlackbodyTable`.
+    Rgb,
+}
+
 fn run() -> Result<()> {
     let args = App::new("starbox")
         .version("0.1")
@@ -38,70 +62,427 @@ fn run() -> Result<()> {
              .help("Number of stars, in thousands")
              .takes_value(true)
              .default_value("500"))
+        .arg(Arg::with_name("color")
+             .long("color")
+             .help("Channel layout to write")
+             .takes_value(true)
+             .possible_values(&["ty", "rgb"])
+             .default_value("ty"))
+        .arg(Arg::with_name("catalog")
+             .long("catalog")
+             .help("Render a real star catalog (HYG CSV layout) instead of a procedural galaxy")
+             .takes_value(true))
+        .arg(Arg::with_name("seed")
+             .long("seed")
+             .help("Base RNG seed, for reproducible procedural galaxies")
+             .takes_value(true)
+             .default_value("0"))
+        .arg(Arg::with_name("psf-radius")
+             .long("psf-radius")
+             .help("Splat each star over a Gaussian PSF of this radius in pixels, instead of binning to the nearest pixel")
+             .takes_value(true))
         .get_matches();
     let res = args.value_of("resolution").unwrap().parse().chain_err(|| "failed to parse resolution")?;
     let number: usize = args.value_of("number").unwrap().parse().chain_err(|| "failed to parse number of stars")?;
     let number = 1000 * number;
-    println!("uncompressed size: {} MiB", res * res * 6 * 2 * 2 / (1024 * 1024));
+    let seed: u32 = args.value_of("seed").unwrap().parse().chain_err(|| "failed to parse seed")?;
+    let color_mode = match args.value_of("color").unwrap() {
+        "rgb" => ColorMode::Rgb,
+        _ => ColorMode::Luminance,
+    };
+    let psf_radius: Option<f32> = match args.value_of("psf-radius") {
+        Some(s) => {
+            let radius: f32 = s.parse().chain_err(|| "failed to parse psf-radius")?;
+            if !radius.is_finite() || radius <= 0.0 || radius > MAX_PSF_RADIUS {
+                bail!("psf-radius must be in (0, {}]", MAX_PSF_RADIUS);
+            }
+            Some(radius)
+        }
+        None => None,
+    };
+    let channels = match color_mode {
+        ColorMode::Luminance => 2,
+        ColorMode::Rgb => 3,
+    };
+    println!("uncompressed size: {} MiB", res * res * 6 * 2 * channels / (1024 * 1024));
     let path = Path::new(args.value_of_os("FILE").unwrap());
     let mut out = File::create(path).chain_err(|| "failed to open output file")?;
-    let mut out = exr::ScanlineOutputFile::new(
-        &mut out,
-        exr::Header::new()
-            .set_resolution(res, 6*res)
-            .set_envmap(Some(exr::Envmap::Cube))
+    let mut header = exr::Header::new()
+        .set_resolution(res, 6*res)
+        .set_envmap(Some(exr::Envmap::Cube));
+    header = match color_mode {
+        ColorMode::Luminance => header
             .add_channel("Y", exr::PixelType::HALF)
-            .add_channel("T", exr::PixelType::HALF))
+            .add_channel("T", exr::PixelType::HALF),
+        ColorMode::Rgb => header
+            .add_channel("R", exr::PixelType::HALF)
+            .add_channel("G", exr::PixelType::HALF)
+            .add_channel("B", exr::PixelType::HALF),
+    };
+    let mut out = exr::ScanlineOutputFile::new(&mut out, header)
         .chain_err(|| "failed to initialize encoder")?;
 
-    let zero = f16::from_f32(0.0);
-    let mut pixel_data: Vec<(f16, f16)> = vec![(zero, zero); (res * 6 * res) as usize];
-    let mut rng = rand::weak_rng();
-    let galaxy = Galaxy::rand(&mut rng);
-    let viewer = galaxy.star(&mut rng).position;
-    let mut max = 0.0;
-    // Kahan summation variables
-    let mut sum = 0.0;
-    let mut c = 0.0;
-    for _ in 0..number {
-        let star = galaxy.star(&mut rng);
+    let blackbody = BlackbodyTable::new();
+
+    let accumulator = if let Some(catalog_path) = args.value_of_os("catalog") {
+        let stars = catalog::load(Path::new(catalog_path)).chain_err(|| "failed to load catalog")?;
+        accumulate_catalog(res, color_mode, &blackbody, psf_radius, &stars)
+    } else {
+        accumulate_galaxy(res, color_mode, &blackbody, psf_radius, number, seed)
+    };
+    println!("brightest star's irradiance: {} fW/m^2\ntotal irradiance: {} fW/m^2",
+             accumulator.max, accumulator.sum);
+
+    {
+        let zero = f16::from_f32(0.0);
+        let max_half: f32 = half::consts::MAX.into();
+        let mut fb = exr::FrameBuffer::new(res, 6*res);
+        match color_mode {
+            ColorMode::Luminance => {
+                let data: Vec<(f16, f16)> = accumulator.pixel_data.iter()
+                    .map(|&(irradiance, channels)| {
+                        if irradiance > 0.0 {
+                            (f16::from_f32(irradiance.min(max_half)), f16::from_f32(channels[0]))
+                        } else {
+                            (zero, zero)
+                        }
+                    })
+                    .collect();
+                fb.insert_channels(&["Y", "T"], &data);
+                out.write_pixels(&fb).chain_err(|| "failed to output data")?;
+            }
+            ColorMode::Rgb => {
+                // Energy conservation: the stored chromaticity is unit-luminance,
+                // so scale each channel by the pixel's accumulated irradiance.
+                let data: Vec<(f16, f16, f16)> = accumulator.pixel_data.iter()
+                    .map(|&(irradiance, channels)| {
+                        (f16::from_f32((channels[0] * irradiance).min(max_half)),
+                         f16::from_f32((channels[1] * irradiance).min(max_half)),
+                         f16::from_f32((channels[2] * irradiance).min(max_half)))
+                    })
+                    .collect();
+                fb.insert_channels(&["R", "G", "B"], &data);
+                out.write_pixels(&fb).chain_err(|| "failed to output data")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Accumulates stars into a cubemap's worth of pixels, tracking total
+/// irradiance (for reporting) alongside each pixel's irradiance-weighted
+/// color channels. Shared by the procedural galaxy and catalog code paths.
+struct Accumulator<'a> {
+    res: u32,
+    color_mode: ColorMode,
+    blackbody: &'a BlackbodyTable,
+    /// When set, stars are splatted over a Gaussian point-spread function of
+    /// this radius (in pixels) instead of binned to their nearest pixel.
+    psf_radius: Option<f32>,
+    /// Per pixel: total irradiance plus up to three irradiance-weighted
+    /// channels (T in Luminance mode; unit-luminance R,G,B chromaticity in
+    /// Rgb mode).
+    pixel_data: Vec<(f32, [f32; 3])>,
+    max: f32,
+    // Kahan summation state for `sum`.
+    sum: f32,
+    c: f32,
+    /// Scratch buffer for `splat`'s kernel samples, reused across stars to
+    /// avoid a heap allocation per star in the hot accumulation loop.
+    splat_samples: Vec<(f32, f32, f32)>,
+}
+
+impl<'a> Accumulator<'a> {
+    fn new(res: u32, color_mode: ColorMode, blackbody: &'a BlackbodyTable, psf_radius: Option<f32>) -> Self {
+        Accumulator {
+            res,
+            color_mode,
+            blackbody,
+            psf_radius,
+            pixel_data: vec![(0.0, [0.0; 3]); (res * 6 * res) as usize],
+            max: 0.0,
+            sum: 0.0,
+            c: 0.0,
+            splat_samples: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, star: &Star, viewer: na::Point3<f32>) {
         let vector = star.position - viewer;
-        let (face, pos) = project(res, vector);
-        let index = address(res, face, pos);
-        let out = &mut pixel_data[index as usize];
-        let old_irradiance: f32 = out.0.into();
-        let old_temp: f32 = out.1.into();
-
-        const SOLAR_LUMINOSITY: f64 = 3.828e26;
-        const GALAXY_RADIUS: f64 = 1e21;
+        let (face, pos) = project(self.res, vector);
+
         // Conversion from solar luminances per galaxy radius^2 to attowatts/m^2
         const SCALING_FACTOR: f32 = (1e15 * (SOLAR_LUMINOSITY / (GALAXY_RADIUS * GALAXY_RADIUS))) as f32;
 
         let irradiance = SCALING_FACTOR * star.intensity / na::norm(&vector).powi(2);
-        if irradiance > max { max = irradiance; }
-        if old_irradiance + irradiance > 0.0 {
-            *out = (f16::from_f32((old_irradiance + irradiance).min(half::consts::MAX.into())),
-                    f16::from_f32((old_temp * old_irradiance + star.temperature * irradiance)
-                                  / (old_irradiance + irradiance)));
+        if irradiance > self.max { self.max = irradiance; }
+        let star_channels = match self.color_mode {
+            ColorMode::Luminance => [star.temperature, 0.0, 0.0],
+            ColorMode::Rgb => {
+                let (r, g, b) = self.blackbody.rgb(star.temperature);
+                [r, g, b]
+            }
+        };
+
+        match self.psf_radius {
+            None => {
+                let index = address(self.res, face, pos);
+                self.deposit(index, irradiance, star_channels);
+            }
+            Some(radius) => self.splat(face, pos, irradiance, star_channels, radius),
         }
 
         // Kahan summation
-        let y = irradiance - c;
-        let t = sum + y;
-        c = (t - sum) - y;
-        sum = t;
+        let y = irradiance - self.c;
+        let t = self.sum + y;
+        self.c = (t - self.sum) - y;
+        self.sum = t;
     }
-    println!("brightest star's irradiance: {} fW/m^2\ntotal irradiance: {} fW/m^2", max, sum);
 
-    {
-        let mut fb = exr::FrameBuffer::new(res, 6*res);
-        fb.insert_channels(&["Y", "T"], &pixel_data);
-        out.write_pixels(&fb).chain_err(|| "failed to output data")?;
+    /// Blends `irradiance`/`channels` into the pixel at `index`, applying the
+    /// same irradiance-weighted blend as merging two partial pixels.
+    fn deposit(&mut self, index: u32, irradiance: f32, channels: [f32; 3]) {
+        let out = &mut self.pixel_data[index as usize];
+        let old_irradiance = out.0;
+        let old_channels = out.1;
+        let total = old_irradiance + irradiance;
+        if total > 0.0 {
+            let mut blended = [0.0f32; 3];
+            for i in 0..3 {
+                blended[i] = (old_channels[i] * old_irradiance + channels[i] * irradiance) / total;
+            }
+            *out = (total, blended);
+        }
     }
 
-    Ok(())
+    /// Distributes `irradiance` over a Gaussian kernel of the given `radius`
+    /// (in pixels) centered on the exact projected `pos`, renormalizing the
+    /// sampled weights so the deposited irradiance sums to the star's true
+    /// irradiance. Kernel samples that fall outside `face`'s bounds are
+    /// reprojected onto the adjacent face rather than clamped, so stars near
+    /// cube-face seams don't lose flux or create visible discontinuities.
+    fn splat(&mut self, face: Face, pos: na::Vector2<f32>, irradiance: f32, channels: [f32; 3], radius: f32) {
+        let sigma = radius / 3.0;
+        let half_width = radius.ceil() as i32;
+        let base_x = pos.x.floor();
+        let base_y = pos.y.floor();
+
+        self.splat_samples.clear();
+        let mut total_weight = 0.0f32;
+        for dy in -half_width..=half_width {
+            for dx in -half_width..=half_width {
+                let px = base_x + dx as f32;
+                let py = base_y + dy as f32;
+                let dist2 = (px - pos.x).powi(2) + (py - pos.y).powi(2);
+                if dist2 > radius * radius { continue; }
+                let weight = (-dist2 / (2.0 * sigma * sigma)).exp();
+                total_weight += weight;
+                self.splat_samples.push((px, py, weight));
+            }
+        }
+        if total_weight <= 0.0 {
+            // Degenerate kernel (e.g. radius below a pixel): fall back to
+            // nearest-pixel binning so the star isn't lost.
+            let index = address(self.res, face, pos);
+            self.deposit(index, irradiance, channels);
+            return;
+        }
+
+        let res = self.res as f32;
+        for i in 0..self.splat_samples.len() {
+            let (px, py, weight) = self.splat_samples[i];
+            let sample_irradiance = irradiance * weight / total_weight;
+            let index = if px >= 0.0 && px < res && py >= 0.0 && py < res {
+                address(self.res, face, na::Vector2::new(px, py))
+            } else {
+                let vector = unproject(self.res, face, na::Vector2::new(px, py));
+                let (true_face, true_pos) = project(self.res, vector);
+                address(self.res, true_face, true_pos)
+            };
+            self.deposit(index, sample_irradiance, channels);
+        }
+    }
+
+    /// Merges another accumulator (e.g. from another worker thread) into this
+    /// one, replicating the irradiance-weighted blend exactly: two partial
+    /// pixels combine the same way two stars landing on the same pixel would.
+    fn merge(mut self, other: Accumulator<'a>) -> Self {
+        for (out, &(other_irradiance, other_channels)) in self.pixel_data.iter_mut().zip(other.pixel_data.iter()) {
+            let (irradiance, channels) = *out;
+            let total = irradiance + other_irradiance;
+            if total > 0.0 {
+                let mut blended = [0.0f32; 3];
+                for i in 0..3 {
+                    blended[i] = (channels[i] * irradiance + other_channels[i] * other_irradiance) / total;
+                }
+                *out = (total, blended);
+            }
+        }
+        if other.max > self.max { self.max = other.max; }
+
+        // Fold the other worker's partial sum in as one more Kahan-compensated term.
+        let y = other.sum - self.c;
+        let t = self.sum + y;
+        self.c = (t - self.sum) - y;
+        self.sum = t;
+
+        self
+    }
 }
 
+/// Per-worker XorShiftRng seed, deterministic in `base_seed` and `worker` so
+/// that parallel runs stay reproducible.
+fn worker_seed(base_seed: u32, worker: u32) -> [u32; 4] {
+    [base_seed, worker, base_seed ^ 0x9e3779b9, worker ^ 0x85ebca6b]
+}
+
+/// Samples `number` stars from a freshly generated procedural galaxy, split
+/// across a rayon thread pool with a private accumulator per worker.
+fn accumulate_galaxy<'a>(res: u32, color_mode: ColorMode, blackbody: &'a BlackbodyTable,
+                         psf_radius: Option<f32>, number: usize, seed: u32) -> Accumulator<'a> {
+    let mut setup_rng = XorShiftRng::from_seed(worker_seed(seed, 0));
+    let galaxy = Galaxy::rand(&mut setup_rng);
+    let viewer = galaxy.star(&mut setup_rng).position;
+
+    let workers = rayon::current_num_threads();
+    let chunk = (number + workers - 1) / workers;
+    (0..workers).into_par_iter()
+        .map(|worker| {
+            let mut rng = XorShiftRng::from_seed(worker_seed(seed, worker as u32 + 1));
+            let mut acc = Accumulator::new(res, color_mode, blackbody, psf_radius);
+            let start = worker * chunk;
+            let end = ((worker + 1) * chunk).min(number);
+            for _ in start..end {
+                let star = galaxy.star(&mut rng);
+                acc.add(&star, viewer);
+            }
+            acc
+        })
+        // `0..workers` always has at least one item, so there's no identity
+        // case to worry about; skip allocating a throwaway zeroed buffer per
+        // `reduce` call that `reduce`'s identity closure would otherwise cost.
+        .reduce_with(Accumulator::merge)
+        .unwrap()
+}
+
+/// Accumulates a fixed catalog of stars, split across a rayon thread pool
+/// with a private accumulator per chunk.
+fn accumulate_catalog<'a>(res: u32, color_mode: ColorMode, blackbody: &'a BlackbodyTable,
+                          psf_radius: Option<f32>, stars: &[Star]) -> Accumulator<'a> {
+    if stars.is_empty() {
+        return Accumulator::new(res, color_mode, blackbody, psf_radius);
+    }
+    let viewer = na::Point3::origin();
+    let workers = rayon::current_num_threads();
+    let chunk_size = ((stars.len() + workers - 1) / workers).max(1);
+    stars.par_chunks(chunk_size)
+        .map(|chunk| {
+            let mut acc = Accumulator::new(res, color_mode, blackbody, psf_radius);
+            for star in chunk {
+                acc.add(star, viewer);
+            }
+            acc
+        })
+        .reduce_with(Accumulator::merge)
+        .unwrap()
+}
+
+/// A star's place on the HR diagram, sampled per `evolutionary_state`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum EvolutionaryState {
+    MainSequence,
+    RedGiant,
+    WhiteDwarf,
+}
+
+// Salpeter (1955) initial mass function, `dN/dm ∝ m^-2.35`.
+const IMF_MASS_MIN: f64 = 0.1;
+const IMF_MASS_MAX: f64 = 50.0;
+const IMF_SLOPE: f64 = 2.35;
+
+/// Inverse CDF of the Salpeter IMF, mapping a uniform `u` in `[0, 1]` to a
+/// mass in `[IMF_MASS_MIN, IMF_MASS_MAX]`. Split out from `sample_salpeter_mass`
+/// so the mapping itself is testable without an `Rng`.
+fn salpeter_inverse_cdf(u: f64) -> f64 {
+    let exponent = 1.0 - IMF_SLOPE;
+    let min_pow = IMF_MASS_MIN.powf(exponent);
+    let max_pow = IMF_MASS_MAX.powf(exponent);
+    (min_pow + u * (max_pow - min_pow)).powf(1.0 / exponent)
+}
+
+/// Draws a star's initial mass from the Salpeter IMF via inverse-CDF sampling.
+fn sample_salpeter_mass<R: Rng>(rng: &mut R) -> f64 {
+    salpeter_inverse_cdf(rng.gen())
+}
+
+// Roughly: low-mass stars sit on the main sequence for longer than the age of
+// the galaxy, so only intermediate and high mass stars have any appreciable
+// chance of being caught past it.
+const GIANT_PROBABILITY_LOW_MASS: f64 = 0.03;
+const WHITE_DWARF_PROBABILITY_LOW_MASS: f64 = 0.02;
+const GIANT_PROBABILITY_HIGH_MASS: f64 = 0.1;
+
+/// Assigns an evolutionary state (main sequence, red giant, white dwarf) with
+/// mass-dependent probabilities.
+fn evolutionary_state<R: Rng>(mass: f64, rng: &mut R) -> EvolutionaryState {
+    let roll: f64 = rng.gen();
+    if mass < 0.5 {
+        EvolutionaryState::MainSequence
+    } else if mass < 8.0 {
+        if roll < WHITE_DWARF_PROBABILITY_LOW_MASS {
+            EvolutionaryState::WhiteDwarf
+        } else if roll < WHITE_DWARF_PROBABILITY_LOW_MASS + GIANT_PROBABILITY_LOW_MASS {
+            EvolutionaryState::RedGiant
+        } else {
+            EvolutionaryState::MainSequence
+        }
+    } else if roll < GIANT_PROBABILITY_HIGH_MASS {
+        EvolutionaryState::RedGiant
+    } else {
+        EvolutionaryState::MainSequence
+    }
+}
+
+// (upper mass bound in solar masses, effective temperature in K at that bound)
+// for O/B/A/F/G/K/M main-sequence spectral classes.
+const MS_SPECTRAL_TABLE: &'static [(f64, f64)] = &[
+    (0.08, 2400.0),  // bottom of M
+    (0.45, 3700.0),  // M/K
+    (0.8, 5200.0),   // K/G
+    (1.04, 6000.0),  // G/F
+    (1.4, 7500.0),   // F/A
+    (2.1, 10000.0),  // A/B
+    (16.0, 30000.0), // B/O
+    (50.0, 50000.0), // top of O
+];
+
+/// Main-sequence effective temperature by mass, log-linearly interpolated
+/// within the O/B/A/F/G/K/M spectral-class table.
+fn main_sequence_temperature(mass: f64) -> f64 {
+    let mass = mass.max(MS_SPECTRAL_TABLE[0].0).min(MS_SPECTRAL_TABLE.last().unwrap().0);
+    for window in MS_SPECTRAL_TABLE.windows(2) {
+        let (m_lo, t_lo) = window[0];
+        let (m_hi, t_hi) = window[1];
+        if mass <= m_hi {
+            let frac = (mass.ln() - m_lo.ln()) / (m_hi.ln() - m_lo.ln());
+            return t_lo + (t_hi - t_lo) * frac;
+        }
+    }
+    MS_SPECTRAL_TABLE.last().unwrap().1
+}
+
+// Red giants swell and cool dramatically relative to their main-sequence
+// progenitor of the same mass.
+const GIANT_LUMINOSITY_BOOST: f64 = 150.0;
+const GIANT_TEMPERATURE_MIN: f64 = 3000.0;
+const GIANT_TEMPERATURE_MAX: f64 = 5000.0;
+
+// White dwarfs are Earth-sized remnants, hot but faint.
+const WHITE_DWARF_RADIUS: f64 = 0.01;
+const WHITE_DWARF_TEMPERATURE_MIN: f64 = 8000.0;
+const WHITE_DWARF_TEMPERATURE_MAX: f64 = 40000.0;
+
 struct Galaxy {
     rotation: na::UnitQuaternion<f32>,
 }
@@ -117,14 +498,15 @@ impl Galaxy {
         // units below are wrt. sol
         //
 
-        let mass = Exp::new(1.0).ind_sample(rng);
+        let mass = sample_salpeter_mass(rng);
+        let state = evolutionary_state(mass, rng);
 
-        let radius = 0.43039846 * mass + 0.52963256; // TODO: Fudge
-
-        // Mass-luminosity relation
+        // Mass-luminosity relation, used directly for main-sequence stars and as
+        // the progenitor luminosity for red giants. White dwarfs don't use it;
+        // their luminosity comes from radius and temperature below.
         // Main-Sequence Effective Temperatures from a Revised Mass-Luminosity Relation Based on Accurate Properties
         // Z. Eker, F. Soydugan, E. Soydugan, S. Bilir, E. Yaz Gokce, I. Steer, M. Tuysuz, T. Senyuz, O. Demircan (2015)
-        let luminosity = if mass <= 1.05 {
+        let ms_luminosity = if mass <= 1.05 {
             4.841132 * mass.ln() - 0.02625
         } else if mass <= 2.40 {
             4.32891 * mass.ln() - 0.00220
@@ -134,7 +516,22 @@ impl Galaxy {
             2.726203 * mass.ln() + 1.237228
         }.exp();
 
-        let temperature = 5777.0 * (luminosity / radius.powi(2)).powf(0.25);
+        let (luminosity, temperature) = match state {
+            EvolutionaryState::MainSequence => (ms_luminosity, main_sequence_temperature(mass)),
+            EvolutionaryState::RedGiant => {
+                let luminosity = ms_luminosity * GIANT_LUMINOSITY_BOOST;
+                let temperature = GIANT_TEMPERATURE_MIN
+                    + (GIANT_TEMPERATURE_MAX - GIANT_TEMPERATURE_MIN) * rng.gen::<f64>();
+                (luminosity, temperature)
+            }
+            EvolutionaryState::WhiteDwarf => {
+                let temperature = WHITE_DWARF_TEMPERATURE_MIN
+                    + (WHITE_DWARF_TEMPERATURE_MAX - WHITE_DWARF_TEMPERATURE_MIN) * rng.gen::<f64>();
+                // Stefan-Boltzmann: L/Lsun = (R/Rsun)^2 * (T/Tsun)^4
+                let luminosity = WHITE_DWARF_RADIUS.powi(2) * (temperature / 5777.0).powi(4);
+                (luminosity, temperature)
+            }
+        };
 
         Star {
             position: pos,
@@ -157,11 +554,11 @@ impl Rand for Galaxy {
     }
 }
 
-struct Star {
-    position: na::Point3<f32>,
-    temperature: f32,
+pub(crate) struct Star {
+    pub(crate) position: na::Point3<f32>,
+    pub(crate) temperature: f32,
     /// Radiant intensity
-    intensity: f32,
+    pub(crate) intensity: f32,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -213,6 +610,23 @@ fn project(res: u32, n: na::Vector3<f32>) -> (Face, na::Vector2<f32>) {
     (face, pos)
 }
 
+/// Inverse of `project`: recovers a (non-unit) direction vector from a pixel
+/// position on `face`, allowing `pos` to extend outside the face's bounds.
+/// Reprojecting the result through `project` finds the true face a splatted
+/// sample lands on when its kernel footprint crosses a seam.
+fn unproject(res: u32, face: Face, pos: na::Vector2<f32>) -> na::Vector3<f32> {
+    let u = 2.0 * pos.x / (res - 1) as f32 - 1.0;
+    let v = 2.0 * pos.y / (res - 1) as f32 - 1.0;
+    match face {
+        Face::PX => na::Vector3::new(1.0, u, v),
+        Face::NX => na::Vector3::new(-1.0, u, v),
+        Face::PY => na::Vector3::new(u, 1.0, v),
+        Face::NY => na::Vector3::new(u, -1.0, v),
+        Face::PZ => na::Vector3::new(u, v, 1.0),
+        Face::NZ => na::Vector3::new(u, v, -1.0),
+    }
+}
+
 fn address(res: u32, face: Face, pos: na::Vector2<f32>) -> u32 {
     let y_min = (face as u32 * res) as f32;
     let y_max = y_min + (res - 1) as f32;
@@ -257,3 +671,82 @@ fn project_sanity() {
     assert_eq!(project(128, -na::Vector3::y()), (Face::NY, na::Vector2::new(63.5, 63.5)));
     assert_eq!(project(128, -na::Vector3::z()), (Face::NZ, na::Vector2::new(63.5, 63.5)));
 }
+
+#[test]
+fn unproject_round_trip() {
+    for &face in &[Face::PX, Face::NX, Face::PY, Face::NY, Face::PZ, Face::NZ] {
+        let pos = na::Vector2::new(10.0, 100.0);
+        let vector = unproject(128, face, pos);
+        assert_eq!(project(128, vector), (face, pos));
+    }
+}
+
+/// A star projected near a cube-face edge, splatted with a kernel wide
+/// enough to overflow onto the neighboring face, must not lose or duplicate
+/// irradiance: the deposited total should match the star's true irradiance.
+#[test]
+fn splat_conserves_energy_across_seam() {
+    let blackbody = BlackbodyTable::new();
+    let mut acc = Accumulator::new(16, ColorMode::Luminance, &blackbody, Some(3.0));
+    let star = Star {
+        position: na::Point3::new(1.0, 0.99, 0.0),
+        temperature: 5778.0,
+        intensity: 1.0,
+    };
+    acc.add(&star, na::Point3::origin());
+    let total: f32 = acc.pixel_data.iter().map(|&(irradiance, _)| irradiance).sum();
+    assert!((total - acc.sum).abs() < acc.sum * 1e-3);
+}
+
+/// Merging two per-worker `Accumulator`s must reproduce exactly what a single
+/// accumulator would have produced processing the same stars sequentially:
+/// the same irradiance-weighted per-pixel blend, and the same Kahan-summed
+/// total irradiance.
+#[test]
+fn merge_matches_sequential_accumulation() {
+    let blackbody = BlackbodyTable::new();
+    let viewer = na::Point3::origin();
+    // Both stars land on the same pixel (both on the +X face, near its center).
+    let star_a = Star { position: na::Point3::new(1.0, 0.1, 0.0), temperature: 5000.0, intensity: 1.0 };
+    let star_b = Star { position: na::Point3::new(1.0, 0.1, 0.0), temperature: 9000.0, intensity: 2.0 };
+
+    let mut sequential = Accumulator::new(4, ColorMode::Luminance, &blackbody, None);
+    sequential.add(&star_a, viewer);
+    sequential.add(&star_b, viewer);
+
+    let mut worker_a = Accumulator::new(4, ColorMode::Luminance, &blackbody, None);
+    worker_a.add(&star_a, viewer);
+    let mut worker_b = Accumulator::new(4, ColorMode::Luminance, &blackbody, None);
+    worker_b.add(&star_b, viewer);
+    let merged = worker_a.merge(worker_b);
+
+    assert_eq!(merged.pixel_data, sequential.pixel_data);
+    assert_eq!(merged.max, sequential.max);
+    assert!((merged.sum - sequential.sum).abs() < 1e-6);
+}
+
+#[test]
+fn salpeter_mass_is_bounded_and_monotonic_in_u() {
+    let us = [0.0, 0.1, 0.25, 0.5, 0.75, 0.9, 1.0];
+    let masses: Vec<f64> = us.iter().cloned().map(salpeter_inverse_cdf).collect();
+    for &m in &masses {
+        assert!(m >= IMF_MASS_MIN && m <= IMF_MASS_MAX, "{} out of [{}, {}]", m, IMF_MASS_MIN, IMF_MASS_MAX);
+    }
+    for w in masses.windows(2) {
+        assert!(w[1] >= w[0], "mass not monotonic in u: {:?}", w);
+    }
+    assert!((masses[0] - IMF_MASS_MIN).abs() < 1e-9);
+    assert!((masses[masses.len() - 1] - IMF_MASS_MAX).abs() < 1e-6);
+}
+
+#[test]
+fn main_sequence_temperature_is_monotonic_and_clamps() {
+    assert_eq!(main_sequence_temperature(0.01), MS_SPECTRAL_TABLE[0].1);
+    assert_eq!(main_sequence_temperature(100.0), MS_SPECTRAL_TABLE.last().unwrap().1);
+
+    let masses = [0.08, 0.2, 0.45, 0.8, 1.04, 1.4, 2.1, 5.0, 16.0, 30.0, 50.0];
+    let temps: Vec<f64> = masses.iter().cloned().map(main_sequence_temperature).collect();
+    for w in temps.windows(2) {
+        assert!(w[1] >= w[0], "temperature not monotonic in mass: {:?}", w);
+    }
+}